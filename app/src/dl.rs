@@ -0,0 +1,280 @@
+//! Downloads episode media, removes SponsorBlock segments, and reports the resulting file stats.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use model::Podcast;
+use serde::Deserialize;
+
+/// Result of processing a single episode: the final (possibly cut) media file and its stats.
+pub struct DownloadResult {
+    pub path: PathBuf,
+    pub length: u64,
+    pub duration: f64,
+    /// How many SponsorBlock segments were cut out of the raw download.
+    pub segments_removed: usize,
+}
+
+/// A `[start, end]` span (in seconds) to be removed, as returned by the SponsorBlock API.
+#[derive(Debug, Deserialize)]
+struct Segment {
+    segment: [f64; 2],
+}
+
+/// Download `video_url`, cut out any configured SponsorBlock segments, and write the result to
+/// `dest`. If no segments match, `dest` ends up being the raw download, untouched. Returns `None`
+/// if SponsorBlock segments cover the entire video, leaving nothing to publish.
+///
+/// `yt-dlp` skips re-downloading a file that already exists at its `-o` path, so the raw download
+/// is always removed before returning, successful or not — otherwise a failure here would wedge
+/// the episode on the same stale raw file forever.
+pub async fn process_episode(
+    podcast: &Podcast,
+    video_id: &str,
+    video_url: &str,
+    dest: &Path,
+) -> eyre::Result<Option<DownloadResult>> {
+    if let Some(parent) = dest.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let raw = dest.with_extension("raw.mp4");
+    let result = cut_episode(podcast, video_id, video_url, dest, &raw).await;
+    std::fs::remove_file(&raw).ok();
+    result
+}
+
+async fn cut_episode(
+    podcast: &Podcast,
+    video_id: &str,
+    video_url: &str,
+    dest: &Path,
+    raw: &Path,
+) -> eyre::Result<Option<DownloadResult>> {
+    download_source(video_url, podcast.downloader_arguments.clone(), raw).await?;
+    let raw_duration = probe_duration(raw).await?;
+
+    let categories = podcast.sponsorblock_categories.clone().unwrap_or_default();
+    let segments = if categories.is_empty() {
+        Vec::new()
+    } else {
+        fetch_sponsor_segments(video_id, &categories).await?
+    };
+    let segments = merge_segments(segments, raw_duration);
+
+    if segments.is_empty() {
+        std::fs::rename(raw, dest)?;
+    } else {
+        let keep = keep_intervals(&segments, raw_duration);
+        if keep.is_empty() {
+            // Every second of the video was marked as sponsor content; there's nothing left to
+            // cut into an episode.
+            return Ok(None);
+        }
+        cut_segments(raw.to_path_buf(), dest.to_path_buf(), keep.clone()).await?;
+    }
+
+    let length = std::fs::metadata(dest)?.len();
+    let duration = probe_duration(dest).await.unwrap_or(raw_duration);
+
+    Ok(Some(DownloadResult {
+        path: dest.to_path_buf(),
+        length,
+        duration,
+        segments_removed: segments.len(),
+    }))
+}
+
+/// Run `yt-dlp` on a blocking-pool thread so a slow download can't stall the async runtime.
+async fn download_source(
+    video_url: &str,
+    extra_args: Option<Vec<String>>,
+    dest: &Path,
+) -> eyre::Result<()> {
+    let video_url = video_url.to_string();
+    let dest = dest.to_path_buf();
+    tokio::task::spawn_blocking(move || {
+        let mut cmd = Command::new("yt-dlp");
+        cmd.arg("-o").arg(&dest).arg(&video_url);
+        if let Some(args) = extra_args {
+            cmd.args(args);
+        }
+        let status = cmd.status()?;
+        eyre::ensure!(status.success(), "yt-dlp exited with {status}");
+        Ok(())
+    })
+    .await?
+}
+
+/// Probe a media file's duration (in seconds) with ffprobe, off the async runtime.
+pub async fn probe_duration(path: &Path) -> eyre::Result<f64> {
+    let path = path.to_path_buf();
+    tokio::task::spawn_blocking(move || {
+        let output = Command::new("ffprobe")
+            .args([
+                "-v",
+                "error",
+                "-show_entries",
+                "format=duration",
+                "-of",
+                "default=noprint_wrapper=1:nokey=1",
+            ])
+            .arg(&path)
+            .output()?;
+        eyre::ensure!(
+            output.status.success(),
+            "ffprobe exited with {}",
+            output.status
+        );
+        Ok(String::from_utf8(output.stdout)?.trim().parse()?)
+    })
+    .await?
+}
+
+async fn fetch_sponsor_segments(
+    video_id: &str,
+    categories: &[String],
+) -> eyre::Result<Vec<(f64, f64)>> {
+    let categories_json = serde_json::to_string(categories)?;
+    let resp = reqwest::get(format!(
+        "https://sponsor.ajay.app/api/skipSegments?videoID={video_id}&categories={categories_json}"
+    ))
+    .await?;
+    if resp.status() == reqwest::StatusCode::NOT_FOUND {
+        // No segments recorded for this video.
+        return Ok(Vec::new());
+    }
+    let segments: Vec<Segment> = resp.error_for_status()?.json().await?;
+    Ok(segments
+        .into_iter()
+        .map(|s| (s.segment[0], s.segment[1]))
+        .collect())
+}
+
+/// Sort and coalesce overlapping/adjacent segments, clamping ends to `duration`.
+fn merge_segments(mut segments: Vec<(f64, f64)>, duration: f64) -> Vec<(f64, f64)> {
+    segments.retain(|(start, _)| *start < duration);
+    for (_, end) in segments.iter_mut() {
+        *end = end.min(duration);
+    }
+    segments.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+
+    let mut merged: Vec<(f64, f64)> = Vec::with_capacity(segments.len());
+    for (start, end) in segments {
+        match merged.last_mut() {
+            Some((_, last_end)) if start <= *last_end => *last_end = last_end.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+    merged
+}
+
+/// Complement of `segments` within `[0, duration]`: the parts of the media to keep.
+fn keep_intervals(segments: &[(f64, f64)], duration: f64) -> Vec<(f64, f64)> {
+    let mut keep = Vec::with_capacity(segments.len() + 1);
+    let mut cursor = 0.0;
+    for (start, end) in segments {
+        if *start > cursor {
+            keep.push((cursor, *start));
+        }
+        cursor = cursor.max(*end);
+    }
+    if cursor < duration {
+        keep.push((cursor, duration));
+    }
+    keep.into_iter()
+        .filter(|(start, end)| end - start > 0.01)
+        .collect()
+}
+
+/// Concatenate the `keep` intervals of `src` into `dest` using ffmpeg's trim/concat filters, off
+/// the async runtime.
+async fn cut_segments(src: PathBuf, dest: PathBuf, keep: Vec<(f64, f64)>) -> eyre::Result<()> {
+    eyre::ensure!(
+        !keep.is_empty(),
+        "no non-empty intervals left to keep for {}",
+        src.display()
+    );
+
+    tokio::task::spawn_blocking(move || {
+        let mut filter = String::new();
+        for (i, (start, end)) in keep.iter().enumerate() {
+            filter.push_str(&format!(
+                "[0:v]trim=start={start}:end={end},setpts=PTS-STARTPTS[v{i}];\
+                 [0:a]atrim=start={start}:end={end},asetpts=PTS-STARTPTS[a{i}];"
+            ));
+        }
+        for i in 0..keep.len() {
+            filter.push_str(&format!("[v{i}][a{i}]"));
+        }
+        filter.push_str(&format!("concat=n={}:v=1:a=1[outv][outa]", keep.len()));
+
+        let status = Command::new("ffmpeg")
+            .arg("-y")
+            .arg("-i")
+            .arg(&src)
+            .arg("-filter_complex")
+            .arg(filter)
+            .args(["-map", "[outv]", "-map", "[outa]"])
+            .arg(&dest)
+            .status()?;
+        eyre::ensure!(status.success(), "ffmpeg exited with {status}");
+        Ok(())
+    })
+    .await?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merge_segments_clamps_ends_to_duration() {
+        assert_eq!(merge_segments(vec![(5.0, 20.0)], 10.0), vec![(5.0, 10.0)]);
+    }
+
+    #[test]
+    fn merge_segments_drops_segments_starting_past_duration() {
+        assert_eq!(merge_segments(vec![(15.0, 20.0)], 10.0), Vec::new());
+    }
+
+    #[test]
+    fn merge_segments_coalesces_overlapping_and_adjacent() {
+        assert_eq!(
+            merge_segments(vec![(10.0, 20.0), (15.0, 25.0), (25.0, 30.0)], 100.0),
+            vec![(10.0, 30.0)]
+        );
+    }
+
+    #[test]
+    fn merge_segments_sorts_out_of_order_input() {
+        assert_eq!(
+            merge_segments(vec![(50.0, 60.0), (0.0, 10.0)], 100.0),
+            vec![(0.0, 10.0), (50.0, 60.0)]
+        );
+    }
+
+    #[test]
+    fn keep_intervals_is_everything_when_no_segments() {
+        assert_eq!(keep_intervals(&[], 30.0), vec![(0.0, 30.0)]);
+    }
+
+    #[test]
+    fn keep_intervals_drops_zero_length_gaps() {
+        // Segments that abut exactly leave no gap in between, and nothing after the last one.
+        assert_eq!(keep_intervals(&[(0.0, 10.0), (10.0, 30.0)], 30.0), Vec::new());
+    }
+
+    #[test]
+    fn keep_intervals_is_empty_when_segment_spans_whole_duration() {
+        assert_eq!(keep_intervals(&[(0.0, 30.0)], 30.0), Vec::new());
+    }
+
+    #[test]
+    fn keep_intervals_is_complement_of_segments() {
+        assert_eq!(
+            keep_intervals(&[(5.0, 10.0), (20.0, 25.0)], 30.0),
+            vec![(0.0, 5.0), (10.0, 20.0), (25.0, 30.0)]
+        );
+    }
+}
@@ -0,0 +1,94 @@
+//! Builds the iTunes-compatible RSS feed for a podcast from its stored episodes.
+
+use std::fs::{self, File};
+use std::path::PathBuf;
+
+use rss::extension::itunes::{ITunesChannelExtensionBuilder, ITunesItemExtensionBuilder};
+use rss::{ChannelBuilder, EnclosureBuilder, GuidBuilder, ItemBuilder};
+use sled::Db;
+
+use model::{
+    episodes_for_podcast, feed_path, feed_url, media_dir, media_url, Episode, EpisodeState,
+    Podcast, Source,
+};
+
+fn build_item(
+    public_base_url: &str,
+    podcast: &Podcast,
+    local_path: &str,
+    length: u64,
+    duration: Option<f64>,
+    episode: &Episode,
+) -> rss::Item {
+    let media_url = media_url(public_base_url, &podcast.name, local_path);
+    let mime = mime_guess::from_path(local_path).first_or_octet_stream().to_string();
+
+    ItemBuilder::default()
+        .title(Some(episode.title.clone()))
+        .guid(Some(
+            GuidBuilder::default()
+                .value(episode.guid.clone())
+                .permalink(false)
+                .build(),
+        ))
+        .pub_date(Some(episode.published.to_rfc2822()))
+        .enclosure(Some(
+            EnclosureBuilder::default()
+                .url(media_url)
+                .mime_type(mime)
+                .length(length.to_string())
+                .build(),
+        ))
+        .itunes_ext(Some(
+            ITunesItemExtensionBuilder::default()
+                .duration(duration.map(|secs| (secs.round() as i64).to_string()))
+                .build(),
+        ))
+        .build()
+}
+
+fn build_channel(db: &Db, podcast: &Podcast, public_base_url: &str) -> Result<rss::Channel, model::Error> {
+    let items: Vec<rss::Item> = episodes_for_podcast(db, &podcast.name)?
+        .iter()
+        .filter(|e| e.state == EpisodeState::Downloaded)
+        .filter_map(|e| {
+            Some(build_item(
+                public_base_url,
+                podcast,
+                e.local_path.as_deref()?,
+                e.length?,
+                e.duration,
+                e,
+            ))
+        })
+        .collect();
+
+    let description = match &podcast.source {
+        Source::Youtube(channel_id) => format!("{} (YouTube channel {channel_id})", podcast.name),
+    };
+
+    Ok(ChannelBuilder::default()
+        .title(podcast.name.clone())
+        .link(feed_url(public_base_url, &podcast.name))
+        .description(description)
+        .itunes_ext(Some(
+            ITunesChannelExtensionBuilder::default()
+                .author(Some(podcast.name.clone()))
+                .build(),
+        ))
+        .items(items)
+        .build())
+}
+
+/// Regenerate `{media_directory}/{podcast}/feed.xml` from the podcast's downloaded episodes.
+pub fn write_podcast_feed(
+    db: &Db,
+    podcast: &Podcast,
+    media_directory: &str,
+    public_base_url: &str,
+) -> eyre::Result<PathBuf> {
+    fs::create_dir_all(media_dir(media_directory, &podcast.name))?;
+    let path = feed_path(media_directory, &podcast.name);
+    build_channel(db, podcast, public_base_url)?.write_to(File::create(&path)?)?;
+    Ok(path)
+}
@@ -1,5 +1,8 @@
 mod dl;
 mod feed;
+mod metrics;
+mod serve;
+mod source;
 
 use std::time;
 
@@ -11,19 +14,193 @@ use hyper::{Method, Server};
 use tower_http::cors::{CorsLayer, Origin};
 
 use async_graphql::http::{playground_source, GraphQLPlaygroundConfig};
-use async_graphql::{EmptySubscription, Schema};
-use async_graphql_axum::{GraphQLRequest, GraphQLResponse};
+use async_graphql::Schema;
+use async_graphql_axum::{GraphQLRequest, GraphQLResponse, GraphQLSubscription};
 use sled::Config;
 
-use model::{ConfigSchedulerExt, MutationRoot, PodcastSchema, QueryRoot, ServerConfig};
-use worker::CancellationToken;
+use model::{
+    ConfigSchedulerExt, MutationRoot, PodcastSchema, ProgressEvent, ProgressSender, ProgressStage,
+    QueryRoot, ServerConfig, SubscriptionRoot,
+};
+use worker::{CancellationToken, JobQueue};
+
+/// How many podcasts may be processed concurrently by the job pool.
+const PODCAST_JOB_CONCURRENCY: usize = 4;
+
+/// Read the server config, falling back to defaults if it's missing or unparsable.
+fn load_config(db: &sled::Db) -> ServerConfig {
+    db.get("config")
+        .ok()
+        .flatten()
+        .and_then(|v| serde_json::from_slice(&v).ok())
+        .unwrap_or_default()
+}
+
+/// Publish a progress event, ignoring the error raised when nobody is subscribed.
+fn emit(
+    progress: &ProgressSender,
+    podcast: &str,
+    stage: ProgressStage,
+    percent: Option<f64>,
+    message: Option<String>,
+) {
+    let _ = progress.send(ProgressEvent {
+        podcast: podcast.to_owned(),
+        stage,
+        percent,
+        message,
+    });
+}
+
+/// Process a single "process podcast X" job: fetch the source feed, download any new episodes
+/// (cutting sponsor segments as configured), and regenerate the podcast's RSS feed.
+async fn process_podcast_job(
+    db: sled::Db,
+    podcasts: sled::Tree,
+    progress: ProgressSender,
+    payload: Vec<u8>,
+) -> eyre::Result<()> {
+    let name = String::from_utf8(payload)?;
+    let Some(raw) = podcasts.get(&name)? else {
+        // Podcast was deleted after the job was enqueued; nothing to do.
+        return Ok(());
+    };
+    let podcast: model::Podcast = serde_json::from_slice(&raw)?;
+
+    emit(&progress, &podcast.name, ProgressStage::FetchingFeed, None, None);
+    let started = time::Instant::now();
+    let result = process_podcast(&db, &podcast, &progress).await;
+    metrics::job_finished(result.is_ok(), started.elapsed());
+    match &result {
+        Ok(()) => emit(&progress, &podcast.name, ProgressStage::Done, None, None),
+        Err(err) => emit(
+            &progress,
+            &podcast.name,
+            ProgressStage::Error,
+            None,
+            Some(err.to_string()),
+        ),
+    }
+    result
+}
+
+async fn process_podcast(
+    db: &sled::Db,
+    podcast: &model::Podcast,
+    progress: &ProgressSender,
+) -> eyre::Result<()> {
+    for episode in source::fetch_episodes(podcast).await? {
+        model::upsert_episode(db, &podcast.name, episode)?;
+    }
+
+    let config = load_config(db);
+    let pending = model::pending_episodes(db, &podcast.name)?;
+    let total = pending.len();
+    let mut failures = Vec::new();
+
+    for (i, episode) in pending.into_iter().enumerate() {
+        let percent = Some((i as f64 / total.max(1) as f64) * 100.0);
+        if podcast
+            .sponsorblock_categories
+            .as_ref()
+            .is_some_and(|cats| !cats.is_empty())
+        {
+            emit(
+                progress,
+                &podcast.name,
+                ProgressStage::CuttingSponsorSegments,
+                percent,
+                Some(episode.title.clone()),
+            );
+        } else {
+            emit(
+                progress,
+                &podcast.name,
+                ProgressStage::Downloading,
+                percent,
+                Some(episode.title.clone()),
+            );
+        }
+
+        let dest =
+            model::media_dir(&config.media_directory, &podcast.name).join(format!("{}.mp4", episode.guid));
+        match dl::process_episode(podcast, &episode.guid, &episode.source_url, &dest).await {
+            Ok(Some(result)) => {
+                let local_path = dest
+                    .file_name()
+                    .expect("dest always has a filename")
+                    .to_string_lossy()
+                    .into_owned();
+                model::mark_episode_downloaded(
+                    db,
+                    &podcast.name,
+                    &episode.guid,
+                    local_path,
+                    result.length,
+                    result.duration,
+                )?;
+                metrics::episode_downloaded(result.length, result.segments_removed as u64);
+            }
+            Ok(None) => model::mark_episode_skipped(db, &podcast.name, &episode.guid)?,
+            Err(err) => {
+                eprintln!(
+                    "failed to download {} ({}): {err:?}",
+                    episode.guid, podcast.name
+                );
+                failures.push(episode.guid);
+            }
+        }
+    }
+
+    emit(progress, &podcast.name, ProgressStage::WritingFeed, None, None);
+    feed::write_podcast_feed(db, podcast, &config.media_directory, &config.public_base_url)?;
+
+    eyre::ensure!(
+        failures.is_empty(),
+        "{} episode(s) failed to download: {}",
+        failures.len(),
+        failures.join(", ")
+    );
+    Ok(())
+}
 
 async fn graphql_handler(schema: Extension<PodcastSchema>, req: GraphQLRequest) -> GraphQLResponse {
     schema.execute(req.0).await.into()
 }
 
 async fn graphql_playground() -> impl IntoResponse {
-    Html(playground_source(GraphQLPlaygroundConfig::new("/")))
+    Html(playground_source(
+        GraphQLPlaygroundConfig::new("/").subscription_endpoint("/ws"),
+    ))
+}
+
+/// Wait for Ctrl-C or SIGTERM, then cancel `cancel_token_root` so schedulers stop and job pools
+/// start draining. Used as axum's graceful-shutdown signal, so the HTTP server also stops
+/// accepting new requests at the same time.
+async fn shutdown_signal(cancel_token_root: CancellationToken) {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("failed to install Ctrl+C handler");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("failed to install SIGTERM handler")
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
+
+    println!("shutdown signal received, stopping schedulers and draining in-flight jobs");
+    cancel_token_root.cancel();
 }
 
 #[tokio::main]
@@ -31,34 +208,27 @@ async fn main() -> eyre::Result<()> {
     let db = Config::new().use_compression(true).path("db.sled").open()?;
 
     let cancel_token_root = CancellationToken::new();
-    let service_worker_db = db.clone();
-    let mut service_worker = worker::Worker::new(
-        move |s| {
-            let mut config = ServerConfig::default();
-            if let Some(stored_config) = service_worker_db.get("config")? {
-                config = serde_json::from_slice(&stored_config)?;
-            }
-            s.new_job_from_config(&config.downloader_schedule)
-                .run(move || async move {
-                    println!(
-                        "{:?} {:?}",
-                        time::SystemTime::now(),
-                        "download worker stuff"
-                    )
-                });
 
-            Ok(())
-        },
+    let storage = db.open_tree("podcasts").expect("cant open podcasts tree");
+
+    let (progress_tx, _) = tokio::sync::broadcast::channel::<ProgressEvent>(256);
+
+    let podcast_queue = JobQueue::new(db.open_tree("jobs_process_podcast")?);
+    let podcast_pool = worker::spawn_pool(
+        podcast_queue.clone(),
+        PODCAST_JOB_CONCURRENCY,
         cancel_token_root.clone(),
-        chrono::Utc, // TODO: Decide if UTC should be the sole Tz we use, maybe making this an option is worth it, IDK.
+        {
+            let db = db.clone();
+            let podcasts = storage.clone();
+            let progress = progress_tx.clone();
+            move |payload| process_podcast_job(db.clone(), podcasts.clone(), progress.clone(), payload)
+        },
     );
-    service_worker.try_schedule()?;
 
-    let podcast_worker_db = db.clone();
-    let storage = podcast_worker_db
-        .open_tree("podcasts")
-        .expect("cant open podcasts tree");
     let podcast_worker_storage = storage.clone();
+    let podcast_worker_progress = progress_tx.clone();
+    let podcast_worker_queue = podcast_queue.clone();
     let mut podcast_worker = worker::Worker::new(
         move |s| {
             let podcasts: Vec<model::Podcast> = podcast_worker_storage
@@ -68,18 +238,29 @@ async fn main() -> eyre::Result<()> {
                 .collect();
 
             for podcast in podcasts {
-                let b = Box::new(podcast);
-                if let Some(run) = &b.update_schedule {
+                if let Some(run) = &podcast.update_schedule {
+                    let queue = podcast_worker_queue.clone();
+                    let name = podcast.name.clone();
+                    let progress = podcast_worker_progress.clone();
                     s.new_job_from_config(run).run(move || {
-                        let podcast = b.clone();
-                        // TODO: Write actual download and feed update logic...
-                        async move { println!("{:?} {:?}", time::SystemTime::now(), podcast.clone()) }
+                        let queue = queue.clone();
+                        let name = name.clone();
+                        let progress = progress.clone();
+                        async move {
+                            match queue.enqueue(name.clone().into_bytes()) {
+                                Ok(()) => {
+                                    metrics::job_enqueued();
+                                    emit(&progress, &name, ProgressStage::Queued, None, None);
+                                }
+                                Err(err) => eprintln!("failed to enqueue job for {name}: {err:?}"),
+                            }
+                        }
                     });
                 }
             }
             Ok(())
         },
-        cancel_token_root,
+        cancel_token_root.clone(),
         chrono::Utc, // TODO: Decide if UTC should be the sole Tz we use, maybe making this an option is worth it, IDK.
     );
     podcast_worker.try_schedule()?;
@@ -93,23 +274,61 @@ async fn main() -> eyre::Result<()> {
         }
     });
 
-    let schema = Schema::build(QueryRoot, MutationRoot, EmptySubscription)
-        .data(db)
+    let config = load_config(&db);
+    let serve_feed_and_media = config.serve_feed_and_media;
+    let shutdown_grace_period = time::Duration::from_secs(config.shutdown_grace_period_secs);
+
+    let schema = Schema::build(QueryRoot, MutationRoot, SubscriptionRoot)
+        .data(db.clone())
+        .data(progress_tx)
+        .data(podcast_queue.clone())
         .finish();
 
     println!("Playground: http://localhost:8000");
 
-    let app = Router::new()
+    let mut app = Router::new()
         .route("/", get(graphql_playground).post(graphql_handler))
+        .route("/ws", GraphQLSubscription::new(schema.clone()))
+        .route("/metrics", get(metrics::handler))
         .layer(Extension(schema))
-        .layer(
-            CorsLayer::new()
-                .allow_origin(Origin::predicate(|_, _| true))
-                .allow_methods(vec![Method::GET, Method::POST]),
-        );
+        .layer(Extension(db.clone()));
+
+    if serve_feed_and_media {
+        app = app
+            .route("/:podcast/feed", get(serve::feed))
+            .route("/:podcast/media/:filename", get(serve::media));
+    }
 
-    Server::bind(&"0.0.0.0:8000".parse().unwrap())
+    let app = app.layer(
+        CorsLayer::new()
+            .allow_origin(Origin::predicate(|_, _| true))
+            .allow_methods(vec![Method::GET, Method::POST]),
+    );
+
+    let cancelled = cancel_token_root.clone();
+    let server = Server::bind(&"0.0.0.0:8000".parse().unwrap())
         .serve(app.into_make_service())
-        .await?;
+        .with_graceful_shutdown(shutdown_signal(cancel_token_root));
+
+    // Run the server until it finishes on its own. It only resolves once a shutdown signal
+    // cancels `cancelled` and in-flight connections close, so the grace period below must only
+    // start counting once that cancellation happens - otherwise an idle server would be killed
+    // ~shutdown_grace_period_secs after startup even with no shutdown signal ever sent.
+    tokio::select! {
+        result = server => result?,
+        _ = async { cancelled.cancelled().await; tokio::time::sleep(shutdown_grace_period).await } => {
+            eprintln!("grace period elapsed with connections still open, exiting anyway");
+        }
+    }
+
+    println!("http server stopped, waiting up to {shutdown_grace_period:?} for in-flight jobs to finish");
+    if tokio::time::timeout(shutdown_grace_period, podcast_pool)
+        .await
+        .is_err()
+    {
+        eprintln!("grace period elapsed with jobs still in flight, exiting anyway");
+    }
+
+    db.flush_async().await?;
     Ok(())
 }
@@ -0,0 +1,112 @@
+//! Prometheus metrics for queue throughput and download activity, served at `/metrics`.
+
+use std::time::Duration;
+
+use axum::extract::Extension;
+use axum::http::{header, StatusCode};
+use axum::response::{IntoResponse, Response};
+use lazy_static::lazy_static;
+use prometheus::{
+    register_histogram, register_int_counter, register_int_counter_vec, register_int_gauge,
+    register_int_gauge_vec, Encoder, Histogram, IntCounter, IntCounterVec, IntGauge, IntGaugeVec,
+    TextEncoder,
+};
+
+lazy_static! {
+    /// Podcasts currently configured.
+    static ref PODCASTS_TOTAL: IntGauge =
+        register_int_gauge!("shinycast_podcasts_total", "Number of configured podcasts").unwrap();
+    /// Stored episodes by their current state.
+    static ref EPISODES_TOTAL: IntGaugeVec = register_int_gauge_vec!(
+        "shinycast_episodes_total",
+        "Number of stored episodes by state",
+        &["state"]
+    )
+    .unwrap();
+    /// Podcast processing jobs enqueued by update schedules. Jobs enqueued through the
+    /// `manuallyProcessPodcast` mutation are not counted.
+    static ref JOBS_ENQUEUED_TOTAL: IntCounter = register_int_counter!(
+        "shinycast_jobs_enqueued_total",
+        "Podcast processing jobs enqueued by update schedules"
+    )
+    .unwrap();
+    /// Podcast processing jobs that finished, by outcome.
+    static ref JOBS_FINISHED_TOTAL: IntCounterVec = register_int_counter_vec!(
+        "shinycast_jobs_finished_total",
+        "Podcast processing jobs that finished, by outcome",
+        &["outcome"]
+    )
+    .unwrap();
+    /// Bytes written for downloaded (and cut) episode media.
+    static ref DOWNLOAD_BYTES_TOTAL: IntCounter = register_int_counter!(
+        "shinycast_download_bytes_total",
+        "Total bytes of episode media downloaded"
+    )
+    .unwrap();
+    /// SponsorBlock segments removed from downloaded episodes.
+    static ref SPONSOR_SEGMENTS_REMOVED_TOTAL: IntCounter = register_int_counter!(
+        "shinycast_sponsor_segments_removed_total",
+        "SponsorBlock segments removed from downloaded episodes"
+    )
+    .unwrap();
+    /// Time spent processing a podcast job, start to finish.
+    static ref PODCAST_RUN_DURATION_SECONDS: Histogram = register_histogram!(
+        "shinycast_podcast_run_duration_seconds",
+        "Time spent processing a podcast job, in seconds"
+    )
+    .unwrap();
+}
+
+/// Record that a podcast processing job was enqueued.
+pub fn job_enqueued() {
+    JOBS_ENQUEUED_TOTAL.inc();
+}
+
+/// Record that a podcast processing job finished, successfully or not, and how long it took.
+pub fn job_finished(succeeded: bool, elapsed: Duration) {
+    JOBS_FINISHED_TOTAL
+        .with_label_values(&[if succeeded { "success" } else { "failure" }])
+        .inc();
+    PODCAST_RUN_DURATION_SECONDS.observe(elapsed.as_secs_f64());
+}
+
+/// Record a downloaded (and possibly cut) episode's final size and how many SponsorBlock
+/// segments were removed from it.
+pub fn episode_downloaded(bytes: u64, segments_removed: u64) {
+    DOWNLOAD_BYTES_TOTAL.inc_by(bytes);
+    SPONSOR_SEGMENTS_REMOVED_TOTAL.inc_by(segments_removed);
+}
+
+/// Refresh the podcast/episode gauges from current storage and render the Prometheus text
+/// exposition format.
+pub async fn handler(Extension(db): Extension<sled::Db>) -> Response {
+    let podcasts = db.open_tree("podcasts").map(|tree| tree.len()).unwrap_or(0);
+    PODCASTS_TOTAL.set(podcasts as i64);
+
+    if let Ok(tree) = db.open_tree("episodes") {
+        let (mut pending, mut downloaded, mut skipped) = (0i64, 0i64, 0i64);
+        for episode in tree
+            .iter()
+            .filter_map(|r| r.ok())
+            .filter_map(|(_, v)| serde_json::from_slice::<model::Episode>(&v).ok())
+        {
+            match episode.state {
+                model::EpisodeState::Pending => pending += 1,
+                model::EpisodeState::Downloaded => downloaded += 1,
+                model::EpisodeState::Skipped => skipped += 1,
+            }
+        }
+        EPISODES_TOTAL.with_label_values(&["pending"]).set(pending);
+        EPISODES_TOTAL
+            .with_label_values(&["downloaded"])
+            .set(downloaded);
+        EPISODES_TOTAL.with_label_values(&["skipped"]).set(skipped);
+    }
+
+    let encoder = TextEncoder::new();
+    let mut buffer = Vec::new();
+    if encoder.encode(&prometheus::gather(), &mut buffer).is_err() {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+    ([(header::CONTENT_TYPE, encoder.format_type())], buffer).into_response()
+}
@@ -0,0 +1,160 @@
+//! Handlers for `/:podcast/feed` and `/:podcast/media/:filename`, registered only when
+//! [`ServerConfig::serve_feed_and_media`](model::ServerConfig::serve_feed_and_media) is enabled.
+
+use axum::body::{boxed, StreamBody};
+use axum::extract::{Extension, Path};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use tokio::fs::File;
+use tokio::io::{AsyncReadExt, AsyncSeekExt, SeekFrom};
+use tokio_util::io::ReaderStream;
+
+use model::{feed_path, media_dir};
+
+use crate::load_config;
+
+/// Reject a path segment that could escape `media_dir` once joined, e.g. via a `/`/`\` component
+/// (path separator smuggled in through axum's percent-decoding) or `..` (traversal).
+fn is_safe_segment(segment: &str) -> bool {
+    !segment.is_empty()
+        && !segment.contains('/')
+        && !segment.contains('\\')
+        && segment != ".."
+}
+
+pub async fn feed(Path(podcast): Path<String>, Extension(db): Extension<sled::Db>) -> Response {
+    if !is_safe_segment(&podcast) {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    let config = load_config(&db);
+    match tokio::fs::read(feed_path(&config.media_directory, &podcast)).await {
+        Ok(body) => ([(header::CONTENT_TYPE, "application/rss+xml")], body).into_response(),
+        Err(_) => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+/// Parse a `Range: bytes=start-end` header into an inclusive `(start, end)` span, clamping `end`
+/// to the last valid byte. Returns `None` if the header is malformed or unsatisfiable.
+fn parse_range(header: &str, size: u64) -> Option<(u64, u64)> {
+    let spec = header.strip_prefix("bytes=")?;
+    let (start, end) = spec.split_once('-')?;
+
+    // `bytes=-500` is a suffix range: the last 500 bytes, not bytes 0..=500.
+    if start.is_empty() && !end.is_empty() {
+        // `bytes=-0` asks for no bytes at all, which can't be satisfied.
+        let suffix_len: u64 = end.parse().ok().filter(|&len| len > 0)?;
+        let end = size.checked_sub(1)?;
+        let start = size.saturating_sub(suffix_len);
+        return Some((start, end));
+    }
+
+    let start: u64 = start.parse().ok()?;
+    let end: u64 = if end.is_empty() {
+        size.checked_sub(1)?
+    } else {
+        end.parse::<u64>().ok()?.min(size.checked_sub(1)?)
+    };
+    if start > end || start >= size {
+        return None;
+    }
+    Some((start, end))
+}
+
+pub async fn media(
+    Path((podcast, filename)): Path<(String, String)>,
+    headers: HeaderMap,
+    Extension(db): Extension<sled::Db>,
+) -> Response {
+    if !is_safe_segment(&podcast) || !is_safe_segment(&filename) {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    let config = load_config(&db);
+    let path = media_dir(&config.media_directory, &podcast).join(&filename);
+
+    let Ok(mut file) = File::open(&path).await else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let Ok(metadata) = file.metadata().await else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let size = metadata.len();
+    let mime = mime_guess::from_path(&filename).first_or_octet_stream();
+
+    let range = headers.get(header::RANGE).and_then(|v| v.to_str().ok());
+    // `end` is only meaningful (and only used) for the `Content-Range` header on a partial
+    // response; the full-file response's `length` is `size` directly, so an empty file correctly
+    // gets a zero-length body instead of `end - start + 1` underflowing into `1`.
+    let (start, end, length, status) = match range {
+        Some(spec) => match parse_range(spec, size) {
+            Some((start, end)) => (start, end, end - start + 1, StatusCode::PARTIAL_CONTENT),
+            None => {
+                return Response::builder()
+                    .status(StatusCode::RANGE_NOT_SATISFIABLE)
+                    .header(header::CONTENT_RANGE, format!("bytes */{size}"))
+                    .body(boxed(axum::body::Empty::new()))
+                    .unwrap();
+            }
+        },
+        None => (0, size.saturating_sub(1), size, StatusCode::OK),
+    };
+
+    if file.seek(SeekFrom::Start(start)).await.is_err() {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+    let body = StreamBody::new(ReaderStream::new(file.take(length)));
+
+    let mut response = Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, mime.to_string())
+        .header(header::ACCEPT_RANGES, "bytes")
+        .header(header::CONTENT_LENGTH, length.to_string());
+    if status == StatusCode::PARTIAL_CONTENT {
+        response = response.header(header::CONTENT_RANGE, format!("bytes {start}-{end}/{size}"));
+    }
+    response.body(boxed(body)).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_range_plain_span() {
+        assert_eq!(parse_range("bytes=0-499", 1000), Some((0, 499)));
+    }
+
+    #[test]
+    fn parse_range_clamps_end_to_last_byte() {
+        assert_eq!(parse_range("bytes=500-", 1000), Some((500, 999)));
+        assert_eq!(parse_range("bytes=0-10000", 1000), Some((0, 999)));
+    }
+
+    #[test]
+    fn parse_range_suffix_range() {
+        assert_eq!(parse_range("bytes=-500", 1000), Some((500, 999)));
+    }
+
+    #[test]
+    fn parse_range_suffix_longer_than_file_clamps_to_start() {
+        assert_eq!(parse_range("bytes=-5000", 1000), Some((0, 999)));
+    }
+
+    #[test]
+    fn parse_range_unsatisfiable_start_past_end() {
+        assert_eq!(parse_range("bytes=1000-", 1000), None);
+        assert_eq!(parse_range("bytes=500-100", 1000), None);
+    }
+
+    #[test]
+    fn parse_range_empty_file_is_unsatisfiable() {
+        assert_eq!(parse_range("bytes=0-", 0), None);
+        assert_eq!(parse_range("bytes=-1", 0), None);
+    }
+
+    #[test]
+    fn parse_range_malformed_header_rejected() {
+        assert_eq!(parse_range("bytes=", 1000), None);
+        assert_eq!(parse_range("items=0-499", 1000), None);
+        assert_eq!(parse_range("bytes=abc-def", 1000), None);
+    }
+}
@@ -0,0 +1,45 @@
+//! Fetches and parses a podcast's upstream source feed into [`Episode`]s.
+
+use atom_syndication::Feed;
+use model::{Episode, EpisodeState, Podcast, Source};
+
+/// Fetch the uploads feed for `podcast`'s source channel, describing each entry as a
+/// not-yet-downloaded episode. Existing download state is preserved by
+/// [`model::upsert_episode`] when these are persisted.
+pub async fn fetch_episodes(podcast: &Podcast) -> eyre::Result<Vec<Episode>> {
+    match &podcast.source {
+        Source::Youtube(channel_id) => fetch_youtube_episodes(channel_id).await,
+    }
+}
+
+async fn fetch_youtube_episodes(channel_id: &str) -> eyre::Result<Vec<Episode>> {
+    let url = format!("https://www.youtube.com/feeds/videos.xml?channel_id={channel_id}");
+    let body = reqwest::get(url).await?.error_for_status()?.bytes().await?;
+    let feed = Feed::read_from(&body[..])?;
+
+    Ok(feed
+        .entries()
+        .iter()
+        .filter_map(|entry| {
+            let guid = entry
+                .id()
+                .strip_prefix("yt:video:")
+                .unwrap_or(entry.id())
+                .to_owned();
+            let source_url = entry.links().first()?.href().to_owned();
+            Some(Episode {
+                guid,
+                title: entry.title().to_string(),
+                published: entry
+                    .published()
+                    .map(|d| d.with_timezone(&chrono::Utc))
+                    .unwrap_or_else(chrono::Utc::now),
+                source_url,
+                state: EpisodeState::Pending,
+                local_path: None,
+                length: None,
+                duration: None,
+            })
+        })
+        .collect())
+}
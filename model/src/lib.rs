@@ -1,17 +1,22 @@
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 
 use async_graphql::{
-    scalar, ComplexObject, Context, EmptySubscription, InputObject, Object, Schema, SimpleObject,
+    scalar, ComplexObject, Context, Enum, InputObject, Object, Schema, SimpleObject, Subscription,
 };
+use futures_util::{Stream, StreamExt};
+use tokio_stream::wrappers::BroadcastStream;
 
 use sled::Db;
 
-use clokwerk::{Interval::Minutes, Job, AsyncScheduler, timeprovider::TimeProvider};
+use clokwerk::{Job, AsyncScheduler, timeprovider::TimeProvider};
+use worker::JobQueue;
 use lazy_static::lazy_static;
 use serde::{Deserialize, Serialize};
 use thiserror::Error as ThisError;
 
-pub type PodcastSchema = Schema<QueryRoot, MutationRoot, EmptySubscription>;
+pub type PodcastSchema = Schema<QueryRoot, MutationRoot, SubscriptionRoot>;
 
 #[derive(Eq, PartialEq, Debug, Copy, Clone, Serialize, Deserialize)]
 pub struct Interval(pub clokwerk::Interval);
@@ -117,14 +122,174 @@ pub struct Podcast {
 
 #[ComplexObject]
 impl Podcast {
-    /// Episodes (query-todo)
-    async fn episodes(&self) -> Option<Vec<bool>> {
-        None
+    /// Episodes that have been seen/downloaded for this podcast.
+    async fn episodes(&self, ctx: &Context<'_>) -> Result<Vec<Episode>, Error> {
+        Ok(episodes_for_podcast(ctx.data_unchecked::<Db>(), &self.name)?)
     }
 
     /// URL to Feed for the podcast, if it's empty it hasn't been processed yet.
-    async fn feed(&self, _ctx: &Context<'_>) -> Option<url::Url> {
-        None
+    async fn feed(&self, ctx: &Context<'_>) -> Result<Option<url::Url>, Error> {
+        let config = server_config(ctx)?;
+        if !feed_path(&config.media_directory, &self.name).exists() {
+            return Ok(None);
+        }
+        Ok(Some(url::Url::parse(&feed_url(
+            &config.public_base_url,
+            &self.name,
+        ))?))
+    }
+}
+
+/// Current position of an episode in the download/cut pipeline.
+#[derive(Eq, PartialEq, Debug, Clone, Copy, Serialize, Deserialize, Enum)]
+pub enum EpisodeState {
+    /// Seen in the source feed, but not downloaded yet.
+    Pending,
+    /// Media has been downloaded (and cut, if configured) and is ready to be served.
+    Downloaded,
+    /// Every second of the source video was removed as SponsorBlock segments, so there was
+    /// nothing left to publish; will not be retried.
+    Skipped,
+}
+
+/// A single episode belonging to a [`Podcast`], persisted in the `episodes` sled tree keyed by
+/// `{podcast}/{guid}`.
+#[derive(Clone, Debug, Serialize, Deserialize, SimpleObject)]
+pub struct Episode {
+    /// Stable identifier for the episode (source video id).
+    pub guid: String,
+    /// Episode title.
+    pub title: String,
+    /// When the episode was published at the source.
+    pub published: chrono::DateTime<chrono::Utc>,
+    /// Where the episode was originally sourced from.
+    pub source_url: String,
+    /// Current processing state.
+    pub state: EpisodeState,
+    /// Filename the episode's media is stored under, relative to the podcast's media directory,
+    /// once downloaded.
+    pub local_path: Option<String>,
+    /// Size of the downloaded media, in bytes, once downloaded.
+    pub length: Option<u64>,
+    /// Duration of the downloaded media, in seconds, once downloaded.
+    pub duration: Option<f64>,
+}
+
+/// Path the generated RSS feed for `name` is written to/served from.
+pub fn feed_path(media_directory: &str, name: &str) -> PathBuf {
+    media_dir(media_directory, name).join("feed.xml")
+}
+
+/// Public URL `name`'s feed is reachable at, given the configured `public_base_url`.
+pub fn feed_url(public_base_url: &str, name: &str) -> String {
+    format!("{public_base_url}/{name}/feed")
+}
+
+/// Public URL an episode's media file is reachable at, given the configured `public_base_url`.
+pub fn media_url(public_base_url: &str, name: &str, filename: &str) -> String {
+    format!("{public_base_url}/{name}/media/{filename}")
+}
+
+/// Directory episode media for `name` is stored under.
+pub fn media_dir(media_directory: &str, name: &str) -> PathBuf {
+    Path::new(media_directory).join(name)
+}
+
+/// Key an [`Episode`] is stored under in the `episodes` tree. `episodes_for_podcast` skips keys
+/// with a further `/` after its `{name}/` prefix, so its scan can't match episodes belonging to a
+/// different, longer podcast name (which [`is_valid_podcast_name`] rejects in the first place).
+pub fn episode_key(podcast: &str, guid: &str) -> Vec<u8> {
+    format!("{podcast}/{guid}").into_bytes()
+}
+
+/// Whether `name` is safe to use as a podcast identifier. Podcast names are used both as a sled
+/// key prefix for that podcast's episodes (`{name}/{guid}`) and as a filesystem directory
+/// component under `media_directory` (see [`media_dir`]), so besides rejecting `/` (which would
+/// let one podcast's prefix scan match another's entries, e.g. `news` matching `news/extra`'s
+/// episodes) this also rejects `.`/`..`, which would otherwise let a podcast's feed/media land
+/// outside `media_directory`.
+pub fn is_valid_podcast_name(name: &str) -> bool {
+    !name.is_empty() && !name.contains('/') && name != "." && name != ".."
+}
+
+/// Insert or update an episode, without clobbering its download state if already present.
+pub fn upsert_episode(db: &Db, podcast: &str, episode: Episode) -> Result<(), Error> {
+    let tree = db.open_tree("episodes")?;
+    let key = episode_key(podcast, &episode.guid);
+    let episode = match tree.get(&key)? {
+        Some(existing) => {
+            let existing: Episode = serde_json::from_slice(&existing)?;
+            Episode { state: existing.state, local_path: existing.local_path, length: existing.length, duration: existing.duration, ..episode }
+        }
+        None => episode,
+    };
+    tree.insert(key, serde_json::to_vec(&episode)?)?;
+    Ok(())
+}
+
+/// Store the result of downloading/cutting `guid`'s media.
+pub fn mark_episode_downloaded(
+    db: &Db,
+    podcast: &str,
+    guid: &str,
+    local_path: String,
+    length: u64,
+    duration: f64,
+) -> Result<(), Error> {
+    let tree = db.open_tree("episodes")?;
+    let key = episode_key(podcast, guid);
+    let Some(raw) = tree.get(&key)? else {
+        return Ok(());
+    };
+    let mut episode: Episode = serde_json::from_slice(&raw)?;
+    episode.state = EpisodeState::Downloaded;
+    episode.local_path = Some(local_path);
+    episode.length = Some(length);
+    episode.duration = Some(duration);
+    tree.insert(key, serde_json::to_vec(&episode)?)?;
+    Ok(())
+}
+
+/// Mark `guid` as [`EpisodeState::Skipped`] so it's no longer returned by [`pending_episodes`].
+pub fn mark_episode_skipped(db: &Db, podcast: &str, guid: &str) -> Result<(), Error> {
+    let tree = db.open_tree("episodes")?;
+    let key = episode_key(podcast, guid);
+    let Some(raw) = tree.get(&key)? else {
+        return Ok(());
+    };
+    let mut episode: Episode = serde_json::from_slice(&raw)?;
+    episode.state = EpisodeState::Skipped;
+    tree.insert(key, serde_json::to_vec(&episode)?)?;
+    Ok(())
+}
+
+/// All episodes stored for `name`, oldest first.
+pub fn episodes_for_podcast(db: &Db, name: &str) -> Result<Vec<Episode>, Error> {
+    let tree = db.open_tree("episodes")?;
+    let prefix = format!("{name}/");
+    let mut episodes: Vec<Episode> = tree
+        .scan_prefix(&prefix)
+        .filter_map(|r| r.ok())
+        // `{name}/extra/{guid}` belongs to a podcast named `{name}/extra`, not to `name`.
+        .filter(|(k, _)| !k[prefix.len()..].contains(&b'/'))
+        .filter_map(|(_, v)| serde_json::from_slice(&v).ok())
+        .collect();
+    episodes.sort_by_key(|e| e.published);
+    Ok(episodes)
+}
+
+/// Episodes for `name` still waiting to be downloaded.
+pub fn pending_episodes(db: &Db, name: &str) -> Result<Vec<Episode>, Error> {
+    Ok(episodes_for_podcast(db, name)?
+        .into_iter()
+        .filter(|e| e.state == EpisodeState::Pending)
+        .collect())
+}
+
+fn server_config(ctx: &Context<'_>) -> Result<ServerConfig, Error> {
+    match ctx.data_unchecked::<Db>().get("config")? {
+        Some(v) => Ok(serde_json::from_slice(&v)?),
+        None => Ok(ServerConfig::default()),
     }
 }
 
@@ -145,28 +310,88 @@ lazy_static! {
 #[derive(Clone, Debug, Serialize, Deserialize, SimpleObject)]
 /// Server configuration, some options might require a restart to take effect.
 pub struct ServerConfig {
-    /// How often should worker responsible for downloading process queue.
-    pub downloader_schedule: ScheduleConfiguration,
     /// Where all media and feed are placed (and served from if enabled).
     pub media_directory: String,
     /// Should the server also serve feeds themselves? By default no.
     /// If enabled this will provide /:podcast/feed & /:podcast/media/:id.ext routes.
     pub serve_feed_and_media: bool,
+    /// Base URL this server is publicly reachable at, used to build feed/media links. Must be
+    /// changed from the default if the server isn't actually reachable at `localhost:8000`.
+    pub public_base_url: String,
+    /// How long, in seconds, to let in-flight download jobs finish after a shutdown signal
+    /// before giving up on them and exiting anyway.
+    pub shutdown_grace_period_secs: u64,
 }
 
 impl Default for ServerConfig {
     fn default() -> Self {
         Self {
-            downloader_schedule: ScheduleConfiguration {
-                base: Interval(Minutes(5)),
-                adjustment: None,
-            },
             media_directory: "media".to_owned(),
             serve_feed_and_media: false,
+            public_base_url: "http://localhost:8000".to_owned(),
+            shutdown_grace_period_secs: 30,
         }
     }
 }
 
+/// Stage of the processing pipeline a [`ProgressEvent`] describes.
+#[derive(Eq, PartialEq, Debug, Clone, Copy, Serialize, Deserialize, Enum)]
+pub enum ProgressStage {
+    /// Enqueued, waiting for a worker to pick it up.
+    Queued,
+    /// Fetching and parsing the source feed for new episodes.
+    FetchingFeed,
+    /// Downloading an episode's media, for podcasts without SponsorBlock categories.
+    Downloading,
+    /// Downloading an episode's media and removing SponsorBlock segments from it, for podcasts
+    /// with SponsorBlock categories; sent in place of `Downloading`, once per episode before the
+    /// download starts. The download and the cut are not reported as separate stages.
+    CuttingSponsorSegments,
+    /// Regenerating the podcast's RSS feed.
+    WritingFeed,
+    /// Processing finished successfully.
+    Done,
+    /// Processing failed.
+    Error,
+}
+
+/// A single step of progress reported while a podcast is processed.
+#[derive(Clone, Debug, Serialize, Deserialize, SimpleObject)]
+pub struct ProgressEvent {
+    /// Podcast this event is about.
+    pub podcast: String,
+    /// What stage of the pipeline this event describes.
+    pub stage: ProgressStage,
+    /// How far along the current stage is, if known (0-100).
+    pub percent: Option<f64>,
+    /// Human-readable detail, e.g. an episode title or error message.
+    pub message: Option<String>,
+}
+
+/// Broadcasts [`ProgressEvent`]s from the job pipeline to GraphQL subscribers.
+pub type ProgressSender = tokio::sync::broadcast::Sender<ProgressEvent>;
+
+pub struct SubscriptionRoot;
+
+#[Subscription]
+impl SubscriptionRoot {
+    /// Stream of processing progress for `name`, or every podcast if omitted.
+    async fn podcast_progress(
+        &self,
+        ctx: &Context<'_>,
+        name: Option<String>,
+    ) -> impl Stream<Item = ProgressEvent> {
+        let rx = ctx.data_unchecked::<ProgressSender>().subscribe();
+        BroadcastStream::new(rx).filter_map(move |event| {
+            let name = name.clone();
+            async move {
+                let event = event.ok()?;
+                (name.is_none() || name.as_deref() == Some(event.podcast.as_str())).then_some(event)
+            }
+        })
+    }
+}
+
 pub struct QueryRoot;
 
 #[Object]
@@ -186,20 +411,39 @@ impl QueryRoot {
 
     /// Server config
     async fn server_config(&self, ctx: &Context<'_>) -> Result<ServerConfig, Error> {
-        let config = ctx
-            .data_unchecked::<Db>()
-            .get("config")?;
-
-        match config {
-            Some(v) => Ok(serde_json::from_slice(&v)?),
-            None => Ok(ServerConfig::default()),
-        }
+        server_config(ctx)
     }
 
     /// Map of allowed sponsorblock categories
     async fn allowed_sponsorblock_categories(&self) -> &HashMap<&str, &str> {
         &SPONSORBLOCK_CATEGORIES
     }
+
+    /// Export all stored podcasts as an OPML subscription list.
+    async fn export_opml(&self, ctx: &Context<'_>) -> Result<String, Error> {
+        let config = server_config(ctx)?;
+        let storage = ctx
+            .data_unchecked::<Db>()
+            .open_tree("podcasts")
+            .expect("cant open podcasts tree");
+
+        export_opml_from(&storage, &config.public_base_url)
+    }
+}
+
+/// Core of [`QueryRoot::export_opml`], operating directly on the `podcasts` tree so it can be
+/// exercised without a GraphQL [`Context`].
+fn export_opml_from(storage: &sled::Tree, public_base_url: &str) -> Result<String, Error> {
+    let mut document = opml::OPML::default();
+    for podcast in storage
+        .iter()
+        .filter_map(|r| r.ok())
+        .filter_map(|(_, p)| serde_json::from_slice::<Podcast>(&p).ok())
+    {
+        let outline = document.add_feed(&podcast.name, &feed_url(public_base_url, &podcast.name));
+        outline.html_url = Some(source_feed_url(&podcast.source));
+    }
+    document.to_string().map_err(|_| Error::OpmlSerialization)
 }
 
 /// Wrapper error types
@@ -216,6 +460,21 @@ pub enum Error {
 
     #[error("server config is missing")]
     ConfigNotFound,
+
+    #[error("invalid url")]
+    Url(#[from] url::ParseError),
+
+    #[error("invalid opml document")]
+    InvalidOpml,
+
+    #[error("podcast name must not be empty, '.', '..', or contain '/'")]
+    InvalidPodcastName,
+
+    #[error("failed to serialize OPML document")]
+    OpmlSerialization,
+
+    #[error("failed to enqueue processing job")]
+    Queue,
 }
 
 pub struct MutationRoot;
@@ -233,6 +492,10 @@ impl MutationRoot {
         sponsorblock_categories: Option<Vec<String>>,
         downloader_arguments: Option<Vec<String>>,
     ) -> Result<bool, Error> {
+        if !is_valid_podcast_name(&name) {
+            return Err(Error::InvalidPodcastName);
+        }
+
         let storage = ctx
             .data_unchecked::<Db>()
             .open_tree("podcasts")
@@ -270,8 +533,7 @@ impl MutationRoot {
             },
             downloader_arguments,
         };
-        storage.insert(name, serde_json::to_vec_pretty(&podcast)?)?;
-        storage.flush_async().await?;
+        store_podcast(&storage, &podcast).await?;
         Ok(true)
     }
 
@@ -280,7 +542,8 @@ impl MutationRoot {
         Ok(false)
     }
 
-    /// Bypass job scheduler and manually start processing of a podcast.
+    /// Bypass the update schedule and enqueue processing of a podcast right away, same as if its
+    /// [`ScheduleConfiguration`](ScheduleConfigurationProposal) had just fired.
     async fn manually_process_podcast(
         &self,
         ctx: &Context<'_>,
@@ -292,12 +555,339 @@ impl MutationRoot {
             .open_tree("podcasts")
             .expect("cant open podcasts tree");
 
-        if let Some(v) = storage.get(name)? {
-            let podcast: Podcast = serde_json::from_slice(&v)?;
-            println!("{:?}", podcast);
-            Ok(true)
-        } else {
-            Err(Error::PodcastNotFound)
+        if storage.get(&name)?.is_none() {
+            return Err(Error::PodcastNotFound);
+        }
+
+        ctx.data_unchecked::<JobQueue>()
+            .enqueue(name.into_bytes())
+            .map_err(|_| Error::Queue)?;
+        Ok(true)
+    }
+
+    /// Import subscriptions from an OPML document, one podcast per outline pointing at a YouTube
+    /// upload feed (via `htmlUrl`, as shinycast exports, or `xmlUrl`, for third-party OPML).
+    /// Existing podcasts with the same name are skipped unless `overwrite_existing` is set.
+    async fn import_opml(
+        &self,
+        ctx: &Context<'_>,
+        opml: String,
+        overwrite_existing: bool,
+    ) -> Result<i32, Error> {
+        let storage = ctx
+            .data_unchecked::<Db>()
+            .open_tree("podcasts")
+            .expect("cant open podcasts tree");
+
+        import_opml_into(&storage, &opml, overwrite_existing).await
+    }
+}
+
+/// Core of [`MutationRoot::import_opml`], operating directly on the `podcasts` tree so it can be
+/// exercised without a GraphQL [`Context`]. Returns the number of podcasts imported.
+async fn import_opml_into(
+    storage: &sled::Tree,
+    opml: &str,
+    overwrite_existing: bool,
+) -> Result<i32, Error> {
+    let document = opml::OPML::from_str(opml).map_err(|_| Error::InvalidOpml)?;
+
+    let mut imported = 0;
+    for outline in flatten_outlines(&document.body.outlines) {
+        // Our own export puts the shinycast feed in `xmlUrl` and the original source feed in
+        // `htmlUrl`; fall back to `xmlUrl` to also accept third-party OPML that points
+        // straight at a YouTube upload feed.
+        let Some(channel_id) = outline
+            .html_url
+            .as_deref()
+            .and_then(channel_id_from_url)
+            .or_else(|| outline.xml_url.as_deref().and_then(channel_id_from_url))
+        else {
+            continue;
+        };
+        let name = outline.title.clone().unwrap_or_else(|| outline.text.clone());
+
+        if !is_valid_podcast_name(&name) {
+            // Can't safely key this podcast's episodes; skip it rather than fail the whole
+            // import.
+            continue;
+        }
+
+        if !overwrite_existing && storage.contains_key(&name)? {
+            continue;
+        }
+
+        let podcast = Podcast {
+            name: name.clone(),
+            source: Source::Youtube(channel_id),
+            update_schedule: None,
+            sponsorblock_categories: None,
+            downloader_arguments: None,
+        };
+        store_podcast(storage, &podcast).await?;
+        imported += 1;
+    }
+    Ok(imported)
+}
+
+/// Write `podcast` to the `podcasts` tree, keyed by name, overwriting any existing entry.
+async fn store_podcast(storage: &sled::Tree, podcast: &Podcast) -> Result<(), Error> {
+    storage.insert(&podcast.name, serde_json::to_vec_pretty(podcast)?)?;
+    storage.flush_async().await?;
+    Ok(())
+}
+
+/// Flatten nested OPML outlines into a single list.
+fn flatten_outlines(outlines: &[opml::Outline]) -> Vec<&opml::Outline> {
+    outlines
+        .iter()
+        .flat_map(|o| std::iter::once(o).chain(flatten_outlines(&o.outlines)))
+        .collect()
+}
+
+/// The upstream feed URL a podcast's `source` is read from. Used as an outline's `htmlUrl` on
+/// export so [`channel_id_from_url`] can recover the source on re-import.
+fn source_feed_url(source: &Source) -> String {
+    match source {
+        Source::Youtube(channel_id) => {
+            format!("https://www.youtube.com/feeds/videos.xml?channel_id={channel_id}")
+        }
+    }
+}
+
+/// Extract `channel_id` from a YouTube upload-feed URL, if the outline points at one.
+fn channel_id_from_url(xml_url: &str) -> Option<String> {
+    url::Url::parse(xml_url)
+        .ok()?
+        .query_pairs()
+        .find(|(k, _)| k == "channel_id")
+        .map(|(_, v)| v.into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_db() -> Db {
+        sled::Config::new().temporary(true).open().unwrap()
+    }
+
+    fn test_episode(guid: &str, published_secs: i64) -> Episode {
+        Episode {
+            guid: guid.to_owned(),
+            title: format!("episode {guid}"),
+            published: chrono::DateTime::from_timestamp(published_secs, 0).unwrap(),
+            source_url: format!("https://example.com/{guid}"),
+            state: EpisodeState::Pending,
+            local_path: None,
+            length: None,
+            duration: None,
         }
     }
+
+    #[test]
+    fn is_valid_podcast_name_rejects_slash_and_empty() {
+        assert!(is_valid_podcast_name("news"));
+        assert!(!is_valid_podcast_name("news/extra"));
+        assert!(!is_valid_podcast_name(""));
+    }
+
+    #[test]
+    fn episodes_for_podcast_does_not_leak_across_colliding_prefixes() {
+        let db = test_db();
+        upsert_episode(&db, "news", test_episode("a", 1)).unwrap();
+        upsert_episode(&db, "news/extra", test_episode("b", 2)).unwrap();
+
+        let news = episodes_for_podcast(&db, "news").unwrap();
+        assert_eq!(news.len(), 1, "must not pick up news/extra's episodes");
+        assert_eq!(news[0].guid, "a");
+    }
+
+    #[test]
+    fn episodes_for_podcast_sorts_oldest_first() {
+        let db = test_db();
+        upsert_episode(&db, "pod", test_episode("newer", 100)).unwrap();
+        upsert_episode(&db, "pod", test_episode("older", 10)).unwrap();
+
+        let episodes = episodes_for_podcast(&db, "pod").unwrap();
+        assert_eq!(
+            episodes.into_iter().map(|e| e.guid).collect::<Vec<_>>(),
+            vec!["older".to_owned(), "newer".to_owned()]
+        );
+    }
+
+    #[test]
+    fn upsert_episode_preserves_download_state_on_re_upsert() {
+        let db = test_db();
+        upsert_episode(&db, "pod", test_episode("a", 1)).unwrap();
+        mark_episode_downloaded(&db, "pod", "a", "a.mp4".to_owned(), 123, 45.0).unwrap();
+
+        // Re-fetching the source feed re-upserts the same episode with fresh metadata; the
+        // existing download state must survive.
+        let mut refreshed = test_episode("a", 1);
+        refreshed.title = "updated title".to_owned();
+        upsert_episode(&db, "pod", refreshed).unwrap();
+
+        let episode = episodes_for_podcast(&db, "pod")
+            .unwrap()
+            .into_iter()
+            .find(|e| e.guid == "a")
+            .unwrap();
+        assert_eq!(episode.title, "updated title");
+        assert_eq!(episode.state, EpisodeState::Downloaded);
+        assert_eq!(episode.local_path, Some("a.mp4".to_owned()));
+        assert_eq!(episode.length, Some(123));
+        assert_eq!(episode.duration, Some(45.0));
+    }
+
+    #[test]
+    fn mark_episode_skipped_is_excluded_from_pending() {
+        let db = test_db();
+        upsert_episode(&db, "pod", test_episode("a", 1)).unwrap();
+        mark_episode_skipped(&db, "pod", "a").unwrap();
+
+        assert!(pending_episodes(&db, "pod").unwrap().is_empty());
+        let episode = &episodes_for_podcast(&db, "pod").unwrap()[0];
+        assert_eq!(episode.state, EpisodeState::Skipped);
+    }
+
+    #[test]
+    fn channel_id_from_url_recovers_what_source_feed_url_wrote() {
+        let source = Source::Youtube("UCabc123".to_owned());
+        let url = source_feed_url(&source);
+        assert_eq!(channel_id_from_url(&url), Some("UCabc123".to_owned()));
+    }
+
+    #[test]
+    fn channel_id_from_url_rejects_unrelated_urls() {
+        assert_eq!(channel_id_from_url("https://example.com/feed.xml"), None);
+    }
+
+    #[test]
+    fn flatten_outlines_walks_nested_groups() {
+        let mut child = opml::Outline::default();
+        child.text = "child".to_owned();
+
+        let mut group = opml::Outline::default();
+        group.text = "group".to_owned();
+        group.outlines = vec![child];
+
+        let outlines = vec![group];
+        let flat = flatten_outlines(&outlines);
+        assert_eq!(
+            flat.into_iter().map(|o| o.text.as_str()).collect::<Vec<_>>(),
+            vec!["group", "child"]
+        );
+    }
+
+    fn test_podcast(name: &str, channel_id: &str) -> Podcast {
+        Podcast {
+            name: name.to_owned(),
+            source: Source::Youtube(channel_id.to_owned()),
+            update_schedule: None,
+            sponsorblock_categories: None,
+            downloader_arguments: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn opml_export_import_round_trips_a_podcast() {
+        let db = test_db();
+        let storage = db.open_tree("podcasts").unwrap();
+        store_podcast(&storage, &test_podcast("news", "UCabc123"))
+            .await
+            .unwrap();
+
+        let opml = export_opml_from(&storage, "http://localhost:8000").unwrap();
+
+        let other_db = test_db();
+        let other_storage = other_db.open_tree("podcasts").unwrap();
+        let imported = import_opml_into(&other_storage, &opml, false).await.unwrap();
+
+        assert_eq!(imported, 1);
+        let raw = other_storage.get("news").unwrap().unwrap();
+        let podcast: Podcast = serde_json::from_slice(&raw).unwrap();
+        assert_eq!(podcast.name, "news");
+        assert_eq!(podcast.source, Source::Youtube("UCabc123".to_owned()));
+    }
+
+    #[tokio::test]
+    async fn import_opml_into_skips_existing_by_default() {
+        let db = test_db();
+        let storage = db.open_tree("podcasts").unwrap();
+        store_podcast(&storage, &test_podcast("news", "UCabc123"))
+            .await
+            .unwrap();
+
+        // Export a different source under the same name to prove it wasn't applied.
+        let other = db.open_tree("other").unwrap();
+        store_podcast(&other, &test_podcast("news", "UCdifferent"))
+            .await
+            .unwrap();
+        let opml = export_opml_from(&other, "http://localhost:8000").unwrap();
+
+        let imported = import_opml_into(&storage, &opml, false).await.unwrap();
+
+        assert_eq!(imported, 0, "existing podcast must be skipped, not overwritten");
+        let raw = storage.get("news").unwrap().unwrap();
+        let podcast: Podcast = serde_json::from_slice(&raw).unwrap();
+        assert_eq!(podcast.source, Source::Youtube("UCabc123".to_owned()));
+    }
+
+    #[tokio::test]
+    async fn import_opml_into_overwrites_existing_when_requested() {
+        let db = test_db();
+        let storage = db.open_tree("podcasts").unwrap();
+        store_podcast(&storage, &test_podcast("news", "UCabc123"))
+            .await
+            .unwrap();
+
+        let other = db.open_tree("other").unwrap();
+        store_podcast(&other, &test_podcast("news", "UCdifferent"))
+            .await
+            .unwrap();
+        let opml = export_opml_from(&other, "http://localhost:8000").unwrap();
+
+        let imported = import_opml_into(&storage, &opml, true).await.unwrap();
+
+        assert_eq!(imported, 1);
+        let raw = storage.get("news").unwrap().unwrap();
+        let podcast: Podcast = serde_json::from_slice(&raw).unwrap();
+        assert_eq!(podcast.source, Source::Youtube("UCdifferent".to_owned()));
+    }
+
+    #[tokio::test]
+    async fn import_opml_into_skips_outlines_with_invalid_names() {
+        let db = test_db();
+        let storage = db.open_tree("other").unwrap();
+        store_podcast(&storage, &test_podcast("news/extra", "UCabc123"))
+            .await
+            .unwrap();
+        let opml = export_opml_from(&storage, "http://localhost:8000").unwrap();
+
+        let target = test_db().open_tree("podcasts").unwrap();
+        let imported = import_opml_into(&target, &opml, false).await.unwrap();
+
+        assert_eq!(imported, 0, "name containing '/' must be skipped");
+    }
+
+    #[tokio::test]
+    async fn import_opml_into_accepts_xml_url_fallback() {
+        let mut outline = opml::Outline::default();
+        outline.text = "news".to_owned();
+        outline.title = Some("news".to_owned());
+        outline.xml_url = Some(source_feed_url(&Source::Youtube("UCabc123".to_owned())));
+
+        let mut document = opml::OPML::default();
+        document.body.outlines = vec![outline];
+        let opml = document.to_string().unwrap();
+
+        let target = test_db().open_tree("podcasts").unwrap();
+        let imported = import_opml_into(&target, &opml, false).await.unwrap();
+
+        assert_eq!(imported, 1, "third-party OPML pointing at xmlUrl must still import");
+        let raw = target.get("news").unwrap().unwrap();
+        let podcast: Podcast = serde_json::from_slice(&raw).unwrap();
+        assert_eq!(podcast.source, Source::Youtube("UCabc123".to_owned()));
+    }
 }
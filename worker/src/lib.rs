@@ -1,5 +1,8 @@
+mod queue;
+
 use chrono::TimeZone;
 pub use clokwerk::{AsyncScheduler, Interval, Job, TimeUnits};
+pub use queue::{spawn_pool, JobQueue};
 use tokio::select;
 pub use tokio_util::sync::CancellationToken;
 
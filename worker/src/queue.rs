@@ -0,0 +1,229 @@
+//! A persistent, at-least-once job queue backed by a sled tree.
+//!
+//! Unlike [`Worker`](crate::Worker), which only decides *when* something should run, a
+//! [`JobQueue`] is responsible for making sure it actually runs: entries survive process restart,
+//! and failed jobs are retried with exponential backoff instead of being dropped on the floor.
+
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sled::{IVec, Tree};
+use tokio::select;
+use tokio::sync::Semaphore;
+use tokio_util::sync::CancellationToken;
+
+/// A job waiting to run, as stored in the queue's sled tree.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct Entry {
+    payload: Vec<u8>,
+    attempts: u32,
+    available_at: DateTime<Utc>,
+}
+
+/// Persistent, at-least-once job queue backed by a sled tree.
+///
+/// Payloads are opaque byte blobs; it's up to the caller to decide what a "job" means. Anything
+/// still in the tree when a [`JobQueue`] is reopened is resumed automatically by [`spawn_pool`].
+#[derive(Clone)]
+pub struct JobQueue {
+    tree: Tree,
+}
+
+impl JobQueue {
+    /// Open (or create) a job queue backed by `tree`.
+    pub fn new(tree: Tree) -> Self {
+        Self { tree }
+    }
+
+    /// Enqueue a job to run as soon as a worker is free.
+    pub fn enqueue(&self, payload: Vec<u8>) -> eyre::Result<()> {
+        let id = self.tree.generate_id()?;
+        let entry = Entry {
+            payload,
+            attempts: 0,
+            available_at: Utc::now(),
+        };
+        self.tree
+            .insert(id.to_be_bytes(), serde_json::to_vec(&entry)?)?;
+        Ok(())
+    }
+
+    /// Jobs currently due to run.
+    fn due(&self) -> Vec<(IVec, Entry)> {
+        let now = Utc::now();
+        self.tree
+            .iter()
+            .filter_map(|r| r.ok())
+            .filter_map(|(k, v)| Some((k, serde_json::from_slice::<Entry>(&v).ok()?)))
+            .filter(|(_, e)| e.available_at <= now)
+            .collect()
+    }
+
+    /// Mark an entry as leased by a worker, pushing its `available_at` out past [`LEASE_DURATION`]
+    /// so `due()` won't hand it to a second worker while it's still running. If the handler never
+    /// completes (e.g. this process crashes mid-job), the lease simply expires and the entry
+    /// becomes due again, preserving at-least-once delivery.
+    fn lease(&self, key: &IVec, mut entry: Entry) -> eyre::Result<()> {
+        entry.available_at = Utc::now() + chrono::Duration::from_std(LEASE_DURATION)?;
+        self.tree.insert(key, serde_json::to_vec(&entry)?)?;
+        Ok(())
+    }
+
+    fn complete(&self, key: &IVec) -> eyre::Result<()> {
+        self.tree.remove(key)?;
+        Ok(())
+    }
+
+    fn reschedule(&self, key: &IVec, mut entry: Entry) -> eyre::Result<()> {
+        entry.attempts += 1;
+        entry.available_at = Utc::now() + chrono::Duration::from_std(backoff(entry.attempts))?;
+        self.tree.insert(key, serde_json::to_vec(&entry)?)?;
+        Ok(())
+    }
+}
+
+/// Exponential backoff between retries, capped at five minutes.
+fn backoff(attempts: u32) -> Duration {
+    Duration::from_secs(2u64.saturating_pow(attempts.min(8)).min(300))
+}
+
+/// How long a leased job may run before it's considered abandoned (e.g. the process crashed
+/// mid-job) and becomes eligible to be picked up again. Comfortably covers a slow download plus
+/// SponsorBlock cut.
+const LEASE_DURATION: Duration = Duration::from_secs(600);
+
+/// Drain `queue` with up to `concurrency` jobs in flight at once, retrying failures with
+/// exponential backoff, until `cancel` fires. Jobs already in `queue` when this is called
+/// (e.g. left over from a previous run) are picked up once due: one the previous run had leased
+/// becomes due when its lease expires, one waiting out a retry backoff when that backoff ends.
+///
+/// The returned [`JoinHandle`](tokio::task::JoinHandle) resolves once every job running at the
+/// moment `cancel` fired has completed, so callers can await it (with a timeout) to drain
+/// in-flight work before shutting down.
+pub fn spawn_pool<F, Fut>(
+    queue: JobQueue,
+    concurrency: usize,
+    cancel: CancellationToken,
+    handler: F,
+) -> tokio::task::JoinHandle<()>
+where
+    F: Fn(Vec<u8>) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = eyre::Result<()>> + Send + 'static,
+{
+    let handler = Arc::new(handler);
+    let semaphore = Arc::new(Semaphore::new(concurrency));
+    tokio::spawn(async move {
+        loop {
+            select! {
+                _ = cancel.cancelled() => break,
+                _ = tokio::time::sleep(Duration::from_secs(1)) => {}
+            }
+
+            for (key, entry) in queue.due() {
+                let Ok(permit) = semaphore.clone().try_acquire_owned() else {
+                    break;
+                };
+                if queue.lease(&key, entry.clone()).is_err() {
+                    continue;
+                }
+                let queue = queue.clone();
+                let handler = handler.clone();
+                tokio::spawn(async move {
+                    let _permit = permit;
+                    match handler(entry.payload.clone()).await {
+                        Ok(()) => {
+                            let _ = queue.complete(&key);
+                        }
+                        Err(err) => {
+                            eprintln!("job failed (attempt {}): {err:?}", entry.attempts + 1);
+                            let _ = queue.reschedule(&key, entry);
+                        }
+                    }
+                });
+            }
+        }
+
+        // Jobs still running hold a permit; waiting to reclaim all of them means waiting for
+        // those jobs to finish.
+        let _ = semaphore.acquire_many_owned(concurrency as u32).await;
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_queue() -> JobQueue {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        JobQueue::new(db.open_tree("jobs").unwrap())
+    }
+
+    #[test]
+    fn enqueued_job_is_immediately_due() {
+        let queue = test_queue();
+        queue.enqueue(b"job".to_vec()).unwrap();
+        assert_eq!(queue.due().len(), 1);
+    }
+
+    #[test]
+    fn leased_entry_is_not_immediately_due_again() {
+        let queue = test_queue();
+        queue.enqueue(b"job".to_vec()).unwrap();
+        let (key, entry) = queue.due().into_iter().next().unwrap();
+
+        queue.lease(&key, entry).unwrap();
+
+        assert!(
+            queue.due().is_empty(),
+            "a second worker must not be able to dequeue an entry already leased to one"
+        );
+    }
+
+    #[test]
+    fn completed_entry_is_removed() {
+        let queue = test_queue();
+        queue.enqueue(b"job".to_vec()).unwrap();
+        let (key, _) = queue.due().into_iter().next().unwrap();
+
+        queue.complete(&key).unwrap();
+
+        assert!(queue.due().is_empty());
+        assert!(queue.tree.get(&key).unwrap().is_none());
+    }
+
+    #[test]
+    fn rescheduled_entry_backs_off_and_bumps_attempts() {
+        let queue = test_queue();
+        queue.enqueue(b"job".to_vec()).unwrap();
+        let (key, entry) = queue.due().into_iter().next().unwrap();
+        assert_eq!(entry.attempts, 0);
+
+        queue.reschedule(&key, entry).unwrap();
+
+        assert!(
+            queue.due().is_empty(),
+            "a failed entry must back off, not be immediately retried"
+        );
+        let raw = queue.tree.get(&key).unwrap().unwrap();
+        let rescheduled: Entry = serde_json::from_slice(&raw).unwrap();
+        assert_eq!(rescheduled.attempts, 1);
+        assert!(rescheduled.available_at > Utc::now());
+    }
+
+    #[test]
+    fn backoff_grows_with_attempts() {
+        assert!(backoff(1) > backoff(0));
+        assert!(backoff(2) > backoff(1));
+    }
+
+    #[test]
+    fn backoff_caps_out() {
+        // `attempts` is clamped to 8 before exponentiating, so anything beyond that is identical.
+        assert_eq!(backoff(8), backoff(9));
+        assert_eq!(backoff(8), backoff(1000));
+        assert!(backoff(1000) <= Duration::from_secs(300));
+    }
+}